@@ -1,54 +1,432 @@
+use std::collections::HashMap;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use notify::{RecursiveMode, Watcher};
+use serde::Deserialize;
 
 pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
 pub static DEFAULT_LOG_LEVEL: &str = "info";
+pub static DEFAULT_BASE_URL: &str = "https://api.example.com";
 
 pub enum ConfigError {
     MissingVar(String),
     InvalidValue(String),
 }
 
+/// A single API credential, optionally scoped to a validity window so keys can be rotated
+/// without downtime: add the new key with a `not_before`, let callers migrate, then set a
+/// `not_after` on the old one instead of deleting it outright.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ApiKey {
+    pub secret: String,
+    pub label: Option<String>,
+    pub not_before: Option<DateTime<Utc>>,
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    fn is_valid_at(&self, now: DateTime<Utc>) -> bool {
+        self.not_before.map_or(true, |t| now >= t) && self.not_after.map_or(true, |t| now < t)
+    }
+}
+
+/// Mirrors `Config`, but every field is optional so a partially-specified file or
+/// environment can be merged against the built-in defaults without losing the
+/// distinction between "unset" and "explicitly set to the default".
+#[derive(Deserialize, Default)]
+struct PartialConfig {
+    api_key: Option<String>,
+    api_keys: Option<String>,
+    base_url: Option<String>,
+    timeout_ms: Option<String>,
+    log_level: Option<String>,
+    log_dir: Option<String>,
+    log_to_file: Option<bool>,
+    rate_limit_max: Option<String>,
+    rate_limit_window: Option<String>,
+    debug: Option<bool>,
+}
+
+impl PartialConfig {
+    fn from_env() -> PartialConfig {
+        PartialConfig {
+            api_key: env::var("API_KEY").ok(),
+            api_keys: env::var("API_KEYS").ok(),
+            base_url: env::var("BASE_URL").ok(),
+            timeout_ms: env::var("TIMEOUT_MS").ok(),
+            log_level: env::var("LOG_LEVEL").ok(),
+            log_dir: env::var("LOG_DIR").ok(),
+            log_to_file: env::var("LOG_TO_FILE").ok().map(|v| v == "true"),
+            rate_limit_max: env::var("RATE_LIMIT_MAX").ok(),
+            rate_limit_window: env::var("RATE_LIMIT_WINDOW").ok(),
+            debug: env::var("DEBUG").ok().map(|v| v == "true"),
+        }
+    }
+
+    fn from_file(path: &Path) -> Result<PartialConfig, ConfigError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| ConfigError::InvalidValue(format!("{}: {}", path.display(), e)))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&contents)
+                .map_err(|e| ConfigError::InvalidValue(format!("{}: {}", path.display(), e))),
+            Some("json") => serde_json::from_str(&contents)
+                .map_err(|e| ConfigError::InvalidValue(format!("{}: {}", path.display(), e))),
+            other => Err(ConfigError::InvalidValue(format!(
+                "{}: unsupported config extension {:?}, expected .toml or .json",
+                path.display(),
+                other
+            ))),
+        }
+    }
+
+    /// Resolves `self` (env) over `other` (file) over the built-in defaults.
+    fn merge(self, other: PartialConfig) -> PartialConfig {
+        PartialConfig {
+            api_key: self.api_key.or(other.api_key),
+            api_keys: self.api_keys.or(other.api_keys),
+            base_url: self.base_url.or(other.base_url),
+            timeout_ms: self.timeout_ms.or(other.timeout_ms),
+            log_level: self.log_level.or(other.log_level),
+            log_dir: self.log_dir.or(other.log_dir),
+            log_to_file: self.log_to_file.or(other.log_to_file),
+            rate_limit_max: self.rate_limit_max.or(other.rate_limit_max),
+            rate_limit_window: self.rate_limit_window.or(other.rate_limit_window),
+            debug: self.debug.or(other.debug),
+        }
+    }
+
+    fn resolve(self) -> Result<Config, ConfigError> {
+        let mut api_keys = Vec::new();
+        if let Some(raw) = self.api_keys {
+            let mut parsed: Vec<ApiKey> = serde_json::from_str(&raw)
+                .map_err(|e| ConfigError::InvalidValue(format!("API_KEYS: {}", e)))?;
+            api_keys.append(&mut parsed);
+        }
+        if let Some(secret) = self.api_key {
+            api_keys.push(ApiKey {
+                secret,
+                label: None,
+                not_before: None,
+                not_after: None,
+            });
+        }
+        if api_keys.is_empty() {
+            return Err(ConfigError::MissingVar("API_KEY".to_string()));
+        }
+        let timeout = match self.timeout_ms {
+            Some(raw) => parse_duration(&raw)?,
+            None => Duration::from_millis(DEFAULT_TIMEOUT_MS),
+        };
+        let rate_limit_max_requests = self
+            .rate_limit_max
+            .map(|raw| {
+                raw.parse::<u32>()
+                    .map_err(|e| ConfigError::InvalidValue(format!("RATE_LIMIT_MAX: {}", e)))
+            })
+            .transpose()?;
+        let rate_limit_window = self
+            .rate_limit_window
+            .map(|raw| parse_duration(&raw))
+            .transpose()?;
+        Ok(Config {
+            api_keys,
+            base_url: self.base_url.unwrap_or_else(|| DEFAULT_BASE_URL.to_string()),
+            timeout_ms: timeout.as_millis() as u64,
+            timeout,
+            log_level: self.log_level.unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string()),
+            log_dir: self.log_dir.map(PathBuf::from),
+            log_to_file: self.log_to_file.unwrap_or(false),
+            rate_limit_max_requests,
+            rate_limit_window,
+            debug: self.debug.unwrap_or(false),
+        })
+    }
+}
+
 pub struct Config {
-    pub api_key: String,
+    pub api_keys: Vec<ApiKey>,
     pub base_url: String,
     pub timeout_ms: u64,
+    pub timeout: Duration,
     pub log_level: String,
+    pub log_dir: Option<PathBuf>,
+    pub log_to_file: bool,
+    pub rate_limit_max_requests: Option<u32>,
+    pub rate_limit_window: Option<Duration>,
     pub debug: bool,
 }
 
 impl Config {
     pub fn from_env() -> Result<Config, ConfigError> {
-        let api_key = env::var("API_KEY")
-            .map_err(|_| ConfigError::MissingVar("API_KEY".to_string()))?;
-        let base_url = env::var("BASE_URL")
-            .unwrap_or_else(|_| "https://api.example.com".to_string());
-        let timeout_ms = env::var("TIMEOUT_MS")
-            .unwrap_or_else(|_| DEFAULT_TIMEOUT_MS.to_string())
-            .parse::<u64>()
-            .map_err(|e| ConfigError::InvalidValue(format!("TIMEOUT_MS: {}", e)))?;
-        Ok(Config {
-            api_key,
-            base_url,
-            timeout_ms,
-            log_level: env::var("LOG_LEVEL").unwrap_or_else(|_| DEFAULT_LOG_LEVEL.to_string()),
-            debug: env::var("DEBUG").unwrap_or_default() == "true",
-        })
+        PartialConfig::from_env().resolve()
+    }
+
+    /// Builds a `Config` from an optional file (TOML or JSON, chosen by extension) with any
+    /// set environment variables overlaid on top, so env vars always win over file values,
+    /// which in turn win over the built-in defaults. Runs `validate` before returning.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let from_file = match path {
+            Some(path) => PartialConfig::from_file(path)?,
+            None => PartialConfig::default(),
+        };
+        let config = PartialConfig::from_env().merge(from_file).resolve()?;
+        config.validate()?;
+        Ok(config)
     }
 
     pub fn validate(&self) -> Result<(), ConfigError> {
-        if self.api_key.is_empty() {
+        if self.api_keys.is_empty() || self.api_keys.iter().any(|k| k.secret.is_empty()) {
             return Err(ConfigError::InvalidValue("API_KEY cannot be empty".to_string()));
         }
+        if self.api_keys.iter().all(|k| k.not_after.is_some_and(|t| t <= Utc::now())) {
+            return Err(ConfigError::InvalidValue(
+                "all configured API keys are expired".to_string(),
+            ));
+        }
         if self.timeout_ms == 0 {
             return Err(ConfigError::InvalidValue("TIMEOUT_MS must be > 0".to_string()));
         }
+        match (self.rate_limit_max_requests, self.rate_limit_window) {
+            (Some(0), Some(_)) => {
+                return Err(ConfigError::InvalidValue(
+                    "RATE_LIMIT_MAX must be > 0 when RATE_LIMIT_WINDOW is set".to_string(),
+                ))
+            }
+            (Some(_), Some(window)) if window.is_zero() => {
+                return Err(ConfigError::InvalidValue(
+                    "RATE_LIMIT_WINDOW must be > 0 when RATE_LIMIT_MAX is set".to_string(),
+                ))
+            }
+            _ => {}
+        }
         Ok(())
     }
 
-    fn parse_log_level(level: &str) -> &str {
+    /// Builds this config's `RateLimiter`, or `None` when rate limiting is left off (the
+    /// default when `RATE_LIMIT_MAX`/`RATE_LIMIT_WINDOW` are unset).
+    pub fn rate_limiter(&self) -> Option<RateLimiter> {
+        match (self.rate_limit_max_requests, self.rate_limit_window) {
+            (Some(max), Some(window)) => Some(RateLimiter::new(max, window)),
+            _ => None,
+        }
+    }
+
+    /// Returns true only when `presented` matches a configured key whose validity window
+    /// contains `now`.
+    pub fn validate_key(&self, presented: &str, now: DateTime<Utc>) -> bool {
+        self.api_keys
+            .iter()
+            .any(|k| k.secret == presented && k.is_valid_at(now))
+    }
+
+    pub fn parse_log_level(level: &str) -> &str {
         match level {
             "debug" | "info" | "warn" | "error" => level,
             _ => DEFAULT_LOG_LEVEL,
         }
     }
+
+    /// Configures a `fern` dispatcher that writes timestamped, leveled lines to stderr and,
+    /// when `log_to_file` is true, to a dated file (`<log_dir>/<YYYY-MM-DD>.log`) under
+    /// `log_dir`. Setting `log_dir` alone does not enable file output; `log_to_file` without
+    /// `log_dir` is an error.
+    pub fn init_logging(&self) -> Result<(), ConfigError> {
+        let level = match Self::parse_log_level(&self.log_level) {
+            "debug" => log::LevelFilter::Debug,
+            "warn" => log::LevelFilter::Warn,
+            "error" => log::LevelFilter::Error,
+            _ => log::LevelFilter::Info,
+        };
+
+        let mut dispatch = fern::Dispatch::new()
+            .format(|out, message, record| {
+                out.finish(format_args!(
+                    "[{} {} {}] {}",
+                    Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+                    record.level(),
+                    record.target(),
+                    message
+                ))
+            })
+            .level(level)
+            .chain(std::io::stderr());
+
+        if self.log_to_file {
+            let log_dir = self
+                .log_dir
+                .as_ref()
+                .ok_or_else(|| ConfigError::InvalidValue("LOG_TO_FILE set but LOG_DIR is not".to_string()))?;
+            fs::create_dir_all(log_dir)
+                .map_err(|e| ConfigError::InvalidValue(format!("{}: {}", log_dir.display(), e)))?;
+            let file_path = log_dir.join(format!("{}.log", Utc::now().format("%Y-%m-%d")));
+            let file = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&file_path)
+                .map_err(|e| ConfigError::InvalidValue(format!("{}: {}", file_path.display(), e)))?;
+            dispatch = dispatch.chain(file);
+        }
+
+        dispatch
+            .apply()
+            .map_err(|e| ConfigError::InvalidValue(format!("logger already initialized: {}", e)))
+    }
+}
+
+struct ClientWindow {
+    start: DateTime<Utc>,
+    count: u32,
+}
+
+/// Whether a `RateLimiter::check` call should proceed, and if not, how long the caller
+/// should wait before trying again.
+pub enum RateLimitDecision {
+    Allowed,
+    Blocked { retry_after: Duration },
+}
+
+/// A fixed-window rate limiter keyed by an arbitrary client identifier (API key, IP, etc.),
+/// so a single misbehaving client can't exhaust the upstream at `base_url` for everyone else.
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    clients: Mutex<HashMap<String, ClientWindow>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> RateLimiter {
+        RateLimiter {
+            max_requests,
+            window,
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Checks and records one request for `key` at `now`. Each client gets its own fixed
+    /// window: once `window` has elapsed since the window's start, the counter resets.
+    pub fn check(&self, key: &str, now: DateTime<Utc>) -> RateLimitDecision {
+        let mut clients = self.clients.lock().expect("rate limiter mutex poisoned");
+        let window =
+            chrono::Duration::from_std(self.window).unwrap_or(chrono::Duration::seconds(i64::MAX));
+        let entry = clients.entry(key.to_string()).or_insert(ClientWindow { start: now, count: 0 });
+
+        if now - entry.start >= window {
+            entry.start = now;
+            entry.count = 1;
+            return RateLimitDecision::Allowed;
+        }
+
+        entry.count += 1;
+        if entry.count <= self.max_requests {
+            RateLimitDecision::Allowed
+        } else {
+            let elapsed = (now - entry.start).to_std().unwrap_or(Duration::ZERO);
+            RateLimitDecision::Blocked {
+                retry_after: self.window.saturating_sub(elapsed),
+            }
+        }
+    }
+}
+
+/// A `Config` that can be rotated live without restarting the process. Readers call
+/// `load()` for a cheap `Arc<Config>` snapshot per request and never observe a half-updated
+/// or invalid config: a reload only publishes once the new config passes `validate`,
+/// otherwise the previous config stays in place.
+pub struct ConfigHandle {
+    inner: ArcSwap<Config>,
+}
+
+impl ConfigHandle {
+    pub fn new(config: Config) -> ConfigHandle {
+        ConfigHandle {
+            inner: ArcSwap::from_pointee(config),
+        }
+    }
+
+    /// Returns a cheap, immutable snapshot of the currently active config.
+    pub fn load(&self) -> Arc<Config> {
+        self.inner.load_full()
+    }
+
+    pub fn reload_from_env(&self) -> Result<(), ConfigError> {
+        let config = Config::from_env()?;
+        config.validate()?;
+        self.inner.store(Arc::new(config));
+        Ok(())
+    }
+
+    pub fn reload_from_file(&self, path: &Path) -> Result<(), ConfigError> {
+        let config = Config::load(Some(path))?;
+        self.inner.store(Arc::new(config));
+        Ok(())
+    }
+
+    /// Watches `path` for modifications and calls `reload_from_file` on each change,
+    /// logging (rather than propagating) reload failures so a bad edit doesn't take down
+    /// the watcher itself. Returns the `notify` watcher; drop it to stop watching.
+    pub fn watch(self: &Arc<Self>, path: PathBuf) -> notify::Result<notify::RecommendedWatcher> {
+        let handle = Arc::clone(self);
+        let watch_path = path.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let Ok(event) = res else { return };
+            if !event.kind.is_modify() {
+                return;
+            }
+            if let Err(e) = handle.reload_from_file(&watch_path) {
+                let message = match e {
+                    ConfigError::MissingVar(v) => v,
+                    ConfigError::InvalidValue(v) => v,
+                };
+                eprintln!(
+                    "config reload from {} failed, keeping previous config: {}",
+                    watch_path.display(),
+                    message
+                );
+            }
+        })?;
+        watcher.watch(path.as_ref(), RecursiveMode::NonRecursive)?;
+        Ok(watcher)
+    }
+}
+
+/// Parses a duration with an explicit unit suffix (`ms`, `s`, `m`, `h`), e.g. `500ms`, `5s`,
+/// `2m`, `1h`. Bare numbers with no suffix are rejected so config values are never ambiguous
+/// about their unit.
+pub fn parse_duration(s: &str) -> Result<Duration, ConfigError> {
+    let s = s.trim();
+    let split_at = s
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| ConfigError::InvalidValue(format!("duration '{}' has no unit suffix", s)))?;
+    let (number, unit) = s.split_at(split_at);
+    if number.is_empty() {
+        return Err(ConfigError::InvalidValue(format!("duration '{}' is missing a number", s)));
+    }
+    let value: u64 = number
+        .parse()
+        .map_err(|e| ConfigError::InvalidValue(format!("duration '{}': {}", s, e)))?;
+    let millis = match unit {
+        "ms" => value,
+        "s" => value
+            .checked_mul(1_000)
+            .ok_or_else(|| ConfigError::InvalidValue(format!("duration '{}' overflows", s)))?,
+        "m" => value
+            .checked_mul(60_000)
+            .ok_or_else(|| ConfigError::InvalidValue(format!("duration '{}' overflows", s)))?,
+        "h" => value
+            .checked_mul(3_600_000)
+            .ok_or_else(|| ConfigError::InvalidValue(format!("duration '{}' overflows", s)))?,
+        other => {
+            return Err(ConfigError::InvalidValue(format!(
+                "duration '{}' has unknown unit '{}'",
+                s, other
+            )))
+        }
+    };
+    Ok(Duration::from_millis(millis))
 }